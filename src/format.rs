@@ -3,6 +3,9 @@ use std::fmt::Display;
 use std::io::Write;
 use time;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use errors::*;
 use facility::Facility;
 use get_hostname;
@@ -10,7 +13,7 @@ use get_process_info;
 use Priority;
 
 #[allow(non_camel_case_types)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub enum Severity {
     LOG_EMERG,
     LOG_ALERT,
@@ -58,14 +61,78 @@ pub trait LogFormat<T> {
     }
 }
 
+/// Wraps any [`LogFormat`] and drops records less urgent than a configurable [`Severity`]
+/// threshold. Admits when `severity as u8 <= threshold as u8`.
+#[derive(Clone, Debug)]
+pub struct LevelFilter<F> {
+    inner: F,
+    level: Severity,
+}
+
+impl<F> LevelFilter<F> {
+    /// Wraps `inner`, admitting only records at least as urgent as `level`.
+    pub fn new(inner: F, level: Severity) -> Self {
+        Self { inner, level }
+    }
+
+    /// Returns the current severity threshold.
+    pub fn level(&self) -> Severity {
+        self.level
+    }
+
+    /// Sets the severity threshold.
+    pub fn set_level(&mut self, level: Severity) {
+        self.level = level;
+    }
+}
+
+impl<T, F: LogFormat<T>> LogFormat<T> for LevelFilter<F> {
+    fn format<W: Write>(&self, w: &mut W, severity: Severity, message: T) -> Result<()> {
+        if severity as u8 <= self.level as u8 {
+            self.inner.format(w, severity, message)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug)]
 pub struct Formatter3164 {
+    #[cfg_attr(feature = "serde", serde(default))]
     pub facility: Facility,
+    #[cfg_attr(feature = "serde", serde(default = "default_hostname"))]
     pub hostname: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default = "default_process"))]
     pub process: String,
+    #[cfg_attr(feature = "serde", serde(default = "default_pid"))]
     pub pid: u32,
 }
 
+/// Auto-detects the hostname for fields left absent in a `serde`-deserialized formatter config.
+#[cfg(feature = "serde")]
+fn default_hostname() -> Option<String> {
+    get_hostname().ok()
+}
+
+/// Auto-detects the current process name for fields left absent in a `serde`-deserialized
+/// formatter config.
+#[cfg(feature = "serde")]
+fn default_process() -> String {
+    get_process_info()
+        .map(|(process, _)| process)
+        .unwrap_or_default()
+}
+
+/// Auto-detects the current pid for fields left absent in a `serde`-deserialized formatter
+/// config.
+#[cfg(feature = "serde")]
+fn default_pid() -> u32 {
+    get_process_info()
+        .map(|(_, pid)| pid)
+        .unwrap_or_else(|_| std::process::id())
+}
+
 impl<T: Display> LogFormat<T> for Formatter3164 {
     fn format<W: Write>(&self, w: &mut W, severity: Severity, message: T) -> Result<()> {
         let format =
@@ -130,55 +197,96 @@ impl Default for Formatter3164 {
     }
 }
 
+/// ANSI SGR escape sequences used by [`FormatterColor`] to highlight a record by [`Severity`].
+mod ansi {
+    pub const RESET: &str = "\x1b[0m";
+    pub const BRIGHT_RED: &str = "\x1b[1;91m";
+    pub const RED: &str = "\x1b[31m";
+    pub const YELLOW: &str = "\x1b[33m";
+    pub const GREEN: &str = "\x1b[32m";
+    pub const BLUE: &str = "\x1b[34m";
+    pub const DIM: &str = "\x1b[2m";
+}
+
+fn ansi_color_for(severity: Severity) -> &'static str {
+    match severity {
+        Severity::LOG_EMERG | Severity::LOG_ALERT | Severity::LOG_CRIT => ansi::BRIGHT_RED,
+        Severity::LOG_ERR => ansi::RED,
+        Severity::LOG_WARNING => ansi::YELLOW,
+        Severity::LOG_NOTICE => ansi::GREEN,
+        Severity::LOG_INFO => ansi::BLUE,
+        Severity::LOG_DEBUG => ansi::DIM,
+    }
+}
+
+/// Wraps a [`Formatter3164`] and highlights each record with an ANSI color chosen by [`Severity`].
+#[derive(Clone, Debug)]
+pub struct FormatterColor {
+    pub inner: Formatter3164,
+    pub colorize: bool,
+}
+
+impl FormatterColor {
+    /// Wraps `inner`, coloring output according to severity.
+    ///
+    /// Set `colorize` to `false` when the writer is not a TTY (e.g. a syslog socket) to emit
+    /// plain RFC 3164 lines with no escape sequences.
+    pub fn new(inner: Formatter3164, colorize: bool) -> Self {
+        Self { inner, colorize }
+    }
+}
+
+impl Default for FormatterColor {
+    /// Returns a `FormatterColor` wrapping `Formatter3164::default()` with coloring enabled.
+    fn default() -> Self {
+        Self::new(Formatter3164::default(), true)
+    }
+}
+
+impl<T: Display> LogFormat<T> for FormatterColor {
+    fn format<W: Write>(&self, w: &mut W, severity: Severity, message: T) -> Result<()> {
+        if !self.colorize {
+            return self.inner.format(w, severity, message);
+        }
+
+        let color = ansi_color_for(severity);
+        self.inner
+            .format(w, severity, format!("{}{}{}", color, message, ansi::RESET))
+    }
+}
+
 /// RFC 5424 structured data
 pub type StructuredData = HashMap<String, HashMap<String, String>>;
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug)]
 pub struct Formatter5424 {
+    #[cfg_attr(feature = "serde", serde(default))]
     pub facility: Facility,
+    #[cfg_attr(feature = "serde", serde(default = "default_hostname"))]
     pub hostname: Option<String>,
     /// Called APP-NAME in RFC5424
+    #[cfg_attr(feature = "serde", serde(default = "default_process"))]
     pub process: String,
+    #[cfg_attr(feature = "serde", serde(default = "default_pid"))]
     pub pid: u32,
 }
 
 impl Formatter5424 {
     pub fn format_5424_structured_data(&self, data: StructuredData) -> String {
-        if data.is_empty() {
-            "-".to_string()
-        } else {
-            let mut res = String::new();
-            for (id, params) in &data {
-                res = res + "[" + id;
-                for (name, value) in params {
-                    res = res + " " + name + "=\"" + &value + "\"";
-                }
-                res += "]";
-            }
-
-            res
-        }
+        render_structured_data(&data)
     }
-}
 
-impl<T: Display> LogFormat<(Option<String>, StructuredData, T)> for Formatter5424 {
-    fn format<W: Write>(
+    /// Writes the common v1 syslog line shared by every `LogFormat` impl on `Formatter5424`,
+    /// given an already-prepared MESSAGE-ID and SD-ELEMENT string.
+    fn format_5424_line<W: Write>(
         &self,
         w: &mut W,
         severity: Severity,
-        log_message: (Option<String>, StructuredData, T),
+        message_id: &str,
+        data: &str,
+        message: impl Display,
     ) -> Result<()> {
-        let (message_id, data, message) = log_message;
-
-        // XXX: seems a lot of effort per-call, we could do this via a wrapper type instead
-        // So the caller could do this once and pass it in
-        let message_id = message_id
-            .unwrap_or_else(|| NILL_VALUE.to_owned())
-            .chars()
-            .filter(is_us_print_ascii)
-            .take(32)
-            .collect::<String>();
-
         // Guard against sub-second precision over 6 digits per rfc5424 section 6
         let timestamp = time::OffsetDateTime::now_utc();
         // SAFETY: timestamp range is enforced, so this will never fail
@@ -201,13 +309,103 @@ impl<T: Display> LogFormat<(Option<String>, StructuredData, T)> for Formatter542
             self.process,
             self.pid,
             message_id,
-            self.format_5424_structured_data(data),
+            data,
             message
         )
         .chain_err(|| ErrorKind::Format)
     }
 }
 
+/// Renders `StructuredData` into its RFC 5424 SD-ELEMENT string.
+fn render_structured_data(data: &StructuredData) -> String {
+    if data.is_empty() {
+        "-".to_string()
+    } else {
+        let mut res = String::new();
+        for (id, params) in data {
+            res = res + "[" + &sanitize_sd_name(id);
+            for (name, value) in params {
+                res = res
+                    + " "
+                    + &sanitize_sd_name(name)
+                    + "=\""
+                    + &escape_5424_param_value(value)
+                    + "\"";
+            }
+            res += "]";
+        }
+
+        res
+    }
+}
+
+/// A MESSAGE-ID, pre-validated and truncated to 32 printable-ASCII characters per rfc5424.
+#[derive(Clone, Debug)]
+pub struct PreparedMessageId(String);
+
+impl PreparedMessageId {
+    /// Validates and truncates `message_id`, or falls back to the rfc5424 nil value (`-`) when
+    /// `None`.
+    pub fn new(message_id: Option<String>) -> Self {
+        let message_id = message_id
+            .unwrap_or_else(|| NILL_VALUE.to_owned())
+            .chars()
+            .filter(is_us_print_ascii)
+            .take(32)
+            .collect();
+        Self(message_id)
+    }
+}
+
+impl Display for PreparedMessageId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// `StructuredData`, pre-rendered into its RFC 5424 SD-ELEMENT string.
+#[derive(Clone, Debug)]
+pub struct PreparedStructuredData(String);
+
+impl PreparedStructuredData {
+    /// Validates and renders `data` into its SD-ELEMENT string.
+    pub fn new(data: StructuredData) -> Self {
+        Self(render_structured_data(&data))
+    }
+}
+
+impl Display for PreparedStructuredData {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<T: Display> LogFormat<(Option<String>, StructuredData, T)> for Formatter5424 {
+    fn format<W: Write>(
+        &self,
+        w: &mut W,
+        severity: Severity,
+        log_message: (Option<String>, StructuredData, T),
+    ) -> Result<()> {
+        let (message_id, data, message) = log_message;
+        let message_id = PreparedMessageId::new(message_id);
+        let data = render_structured_data(&data);
+        self.format_5424_line(w, severity, &message_id.0, &data, message)
+    }
+}
+
+impl<T: Display> LogFormat<(PreparedMessageId, &PreparedStructuredData, T)> for Formatter5424 {
+    fn format<W: Write>(
+        &self,
+        w: &mut W,
+        severity: Severity,
+        log_message: (PreparedMessageId, &PreparedStructuredData, T),
+    ) -> Result<()> {
+        let (message_id, data, message) = log_message;
+        self.format_5424_line(w, severity, &message_id.0, &data.0, message)
+    }
+}
+
 impl<T: Display> LogFormat<(u32, StructuredData, T)> for Formatter5424 {
     fn format<W: Write>(
         &self,
@@ -266,6 +464,29 @@ fn is_us_print_ascii(c: &char) -> bool {
     33 <= *c as u32 && *c as u32 <= 126
 }
 
+/// Checks if a character is valid in an SD-NAME (an SD-ID or a PARAM-NAME).
+/// Defined by rfc5424 §6.3.2 as printable US ASCII excluding `=`, SP, `]`, and `"`.
+fn is_sd_name_char(c: &char) -> bool {
+    is_us_print_ascii(c) && !matches!(c, '=' | ' ' | ']' | '"')
+}
+
+/// Sanitizes an SD-ID or PARAM-NAME by dropping characters that aren't valid SD-NAME characters.
+fn sanitize_sd_name(name: &str) -> String {
+    name.chars().filter(is_sd_name_char).collect()
+}
+
+/// Escapes `\`, `"`, and `]` in a PARAM-VALUE, as required by rfc5424 §6.3.3.
+fn escape_5424_param_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || c == '"' || c == ']' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 fn encode_priority(severity: Severity, facility: Facility) -> Priority {
     facility as u8 | severity as u8
 }
@@ -338,6 +559,120 @@ mod test {
         // Can't really make any assertions about the pid.
     }
 
+    #[test]
+    fn formatter_color_wraps_message_in_escapes_when_enabled() {
+        let mut buf = Vec::new();
+        let f = FormatterColor::new(Formatter3164::default(), true);
+        f.format(&mut buf, Severity::LOG_ERR, "boom").unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.contains(ansi::RED));
+        assert!(out.ends_with(&format!("boom{}", ansi::RESET)));
+    }
+
+    #[test]
+    fn formatter_color_passes_through_unmodified_when_disabled() {
+        let mut colored = Vec::new();
+        let mut plain = Vec::new();
+        let f = FormatterColor::new(Formatter3164::default(), false);
+        f.inner.format(&mut plain, Severity::LOG_ERR, "boom").unwrap();
+        f.format(&mut colored, Severity::LOG_ERR, "boom").unwrap();
+        assert_eq!(colored, plain);
+    }
+
+    #[test]
+    fn structured_data_escapes_quotes_and_brackets_in_values() {
+        let f = Formatter5424::default();
+        let mut data = StructuredData::new();
+        let mut params = HashMap::new();
+        params.insert("msg".to_string(), r#"say "hi" [ok]\now"#.to_string());
+        data.insert("exampleSDID@32473".to_string(), params);
+
+        let rendered = f.format_5424_structured_data(data);
+        assert_eq!(
+            rendered,
+            r#"[exampleSDID@32473 msg="say \"hi\" [ok\]\\now"]"#
+        );
+    }
+
+    #[test]
+    fn structured_data_sanitizes_sd_id_and_param_names() {
+        let f = Formatter5424::default();
+        let mut data = StructuredData::new();
+        let mut params = HashMap::new();
+        params.insert("na me=bad\"".to_string(), "value".to_string());
+        data.insert("id]bad".to_string(), params);
+
+        let rendered = f.format_5424_structured_data(data);
+        assert_eq!(rendered, r#"[idbad namebad="value"]"#);
+    }
+
+    #[test]
+    fn level_filter_admits_records_at_or_above_threshold() {
+        let mut buf = Vec::new();
+        let f = LevelFilter::new(Formatter3164::default(), Severity::LOG_WARNING);
+        f.format(&mut buf, Severity::LOG_ERR, "boom").unwrap();
+        assert!(!buf.is_empty());
+    }
+
+    #[test]
+    fn level_filter_drops_records_below_threshold() {
+        let mut buf = Vec::new();
+        let f = LevelFilter::new(Formatter3164::default(), Severity::LOG_WARNING);
+        f.format(&mut buf, Severity::LOG_DEBUG, "chatter").unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn level_filter_set_level_changes_threshold() {
+        let mut f = LevelFilter::new(Formatter3164::default(), Severity::LOG_ERR);
+        f.set_level(Severity::LOG_DEBUG);
+        assert!(matches!(f.level(), Severity::LOG_DEBUG));
+
+        let mut buf = Vec::new();
+        f.format(&mut buf, Severity::LOG_DEBUG, "now visible").unwrap();
+        assert!(!buf.is_empty());
+    }
+
+    #[test]
+    fn prepared_structured_data_matches_owned_rendering() {
+        let f = Formatter5424::default();
+        let mut data = StructuredData::new();
+        let mut params = HashMap::new();
+        params.insert("msg".to_string(), "hi".to_string());
+        data.insert("exampleSDID@32473".to_string(), params);
+
+        let owned = f.format_5424_structured_data(data.clone());
+        let prepared = PreparedStructuredData::new(data);
+        assert_eq!(owned, prepared.to_string());
+    }
+
+    #[test]
+    fn prepared_message_id_truncates_and_filters() {
+        let long_id = "a ".repeat(40) + " with spaces";
+        let prepared = PreparedMessageId::new(Some(long_id));
+        assert_eq!(prepared.to_string(), "a".repeat(32));
+    }
+
+    #[test]
+    fn prepared_message_id_falls_back_to_nil_value() {
+        let prepared = PreparedMessageId::new(None);
+        assert_eq!(prepared.to_string(), "-");
+    }
+
+    #[test]
+    fn formatter5424_accepts_prepared_structured_data() {
+        let f = Formatter5424::default();
+        let message_id = PreparedMessageId::new(Some("msgid".to_string()));
+        let data = PreparedStructuredData::new(StructuredData::new());
+
+        let mut buf = Vec::new();
+        f.format(&mut buf, Severity::LOG_INFO, (message_id, &data, "hello"))
+            .unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.contains("msgid"));
+        assert!(out.ends_with("hello"));
+    }
+
     #[test]
     fn test_formatter5424_defaults() {
         let d = Formatter5424::default();